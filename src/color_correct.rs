@@ -0,0 +1,207 @@
+// Copyright 2021 Jerónimo Sánchez <jeronimosg@hotmail.es>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//   http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use image::RgbImage;
+
+use crate::{Executor, Feature, ImgprocGpuError};
+
+impl Executor {
+    /// Multiplies every pixel's RGB vector by `matrix`, clamping the result
+    /// back to the 0-255 range.
+    pub fn apply_ccm(
+        &self,
+        img: &RgbImage,
+        matrix: [[f32; 3]; 3],
+    ) -> Result<RgbImage, ImgprocGpuError> {
+        let src = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let dest = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_WRITE_ONLY
+                    | ocl::flags::MEM_HOST_READ_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let flat_matrix: Vec<f32> = matrix.iter().flatten().copied().collect();
+        let matrix_buffer = ocl::Buffer::<f32>::builder()
+            .queue(self.queue.clone())
+            .len(flat_matrix.len())
+            .copy_host_slice(&flat_matrix)
+            .build()
+            .map_err(ImgprocGpuError::BufferAlloc)?;
+
+        let dims = img.dimensions();
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::ColorCorrect)?)
+            .name("apply_ccm")
+            .queue(self.queue.clone())
+            .global_work_size(&dims)
+            .arg(&src)
+            .arg(&dest)
+            .arg(&matrix_buffer)
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "apply_ccm",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "apply_ccm",
+                    source,
+                })?;
+        }
+
+        let mut output = image::ImageBuffer::new(dims.0, dims.1);
+
+        dest.read(&mut output)
+            .enq()
+            .map_err(ImgprocGpuError::MemRead)?;
+
+        Ok(output)
+    }
+
+    /// White-balances `img` for `temperature_k` by interpolating between the
+    /// two calibration CCMs in `calib` that bracket it, then applying the
+    /// result with [`Executor::apply_ccm`]. `calib` must be sorted ascending
+    /// by temperature; temperatures outside its range clamp to the nearest
+    /// endpoint's matrix.
+    pub fn white_balance(
+        &self,
+        img: &RgbImage,
+        temperature_k: f32,
+        calib: &[(f32, [[f32; 3]; 3])],
+    ) -> Result<RgbImage, ImgprocGpuError> {
+        let matrix = interpolate_ccm(temperature_k, calib);
+        self.apply_ccm(img, matrix)
+    }
+}
+
+/// Linearly interpolates between the two `calib` entries bracketing
+/// `temperature_k`, clamping to the nearest endpoint outside its range.
+fn interpolate_ccm(temperature_k: f32, calib: &[(f32, [[f32; 3]; 3])]) -> [[f32; 3]; 3] {
+    assert!(!calib.is_empty(), "calib must have at least one entry");
+
+    if temperature_k <= calib[0].0 {
+        return calib[0].1;
+    }
+
+    if temperature_k >= calib[calib.len() - 1].0 {
+        return calib[calib.len() - 1].1;
+    }
+
+    let (lo, hi) = calib
+        .windows(2)
+        .map(|w| (w[0], w[1]))
+        .find(|(lo, hi)| temperature_k >= lo.0 && temperature_k <= hi.0)
+        .expect("temperature_k within calib's range but no bracketing pair found");
+
+    let t = (temperature_k - lo.0) / (hi.0 - lo.0);
+
+    let mut result = [[0.0f32; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            result[i][j] = lo.1[i][j] * (1.0 - t) + hi.1[i][j] * t;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    const DOUBLE: [[f32; 3]; 3] = [[2.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 2.0]];
+
+    #[test]
+    fn apply_ccm_doubles_intensity_and_clamps() {
+        let executor = Executor::default();
+
+        let image = RgbImage::from_pixel(2, 2, image::Rgb([100, 10, 200]));
+        let result = executor.apply_ccm(&image, DOUBLE).unwrap();
+
+        let expected = RgbImage::from_pixel(2, 2, image::Rgb([200, 20, 255]));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn apply_ccm_rounds_fractional_results_instead_of_truncating() {
+        let executor = Executor::default();
+
+        // 100 * 1.5 = 150.0 exactly, but 10 * 1.5 = 15.0 would truncate to
+        // 15 either way; use a coefficient whose product lands past the
+        // half-way point to actually distinguish round() from a cast-only
+        // truncation (floor for positive values).
+        const ONE_AND_A_HALF: [[f32; 3]; 3] =
+            [[1.5, 0.0, 0.0], [0.0, 1.5, 0.0], [0.0, 0.0, 1.5]];
+
+        let image = RgbImage::from_pixel(2, 2, image::Rgb([11, 0, 0]));
+        let result = executor.apply_ccm(&image, ONE_AND_A_HALF).unwrap();
+
+        // 11 * 1.5 = 16.5, which rounds to 17 but truncates to 16.
+        let expected = RgbImage::from_pixel(2, 2, image::Rgb([17, 0, 0]));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn white_balance_at_calibrated_temperature_matches_apply_ccm() {
+        let executor = Executor::default();
+        let calib = [(2700.0, IDENTITY), (6500.0, DOUBLE)];
+
+        let image = RgbImage::from_pixel(2, 2, image::Rgb([100, 10, 200]));
+
+        let expected = executor.apply_ccm(&image, DOUBLE).unwrap();
+        let actual = executor.white_balance(&image, 6500.0, &calib).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn interpolate_ccm_at_midpoint_averages_coefficients() {
+        let calib = [(2700.0, IDENTITY), (6500.0, DOUBLE)];
+
+        let matrix = interpolate_ccm(4600.0, &calib);
+        assert_eq!(matrix, [[1.5, 0.0, 0.0], [0.0, 1.5, 0.0], [0.0, 0.0, 1.5]]);
+    }
+
+    #[test]
+    fn interpolate_ccm_clamps_below_range() {
+        let calib = [(2700.0, IDENTITY), (6500.0, DOUBLE)];
+
+        let matrix = interpolate_ccm(1000.0, &calib);
+        assert_eq!(matrix, IDENTITY);
+    }
+
+    #[test]
+    fn interpolate_ccm_clamps_above_range() {
+        let calib = [(2700.0, IDENTITY), (6500.0, DOUBLE)];
+
+        let matrix = interpolate_ccm(10000.0, &calib);
+        assert_eq!(matrix, DOUBLE);
+    }
+}
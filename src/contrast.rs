@@ -12,12 +12,217 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use image::GrayImage;
+use image::{GrayImage, Luma};
 
-use crate::{Executor, Feature};
+use crate::{Executor, Feature, GpuImage, ImgprocGpuError};
 
 impl Executor {
-    pub fn threshold(&self, img: &GrayImage, threshold: u8) -> GrayImage {
+    /// Device-resident counterpart of [`threshold`](Executor::threshold):
+    /// takes and returns a [`GpuImage`] so chaining it with other `_gpu`
+    /// operations needs no host round-trip in between.
+    pub fn threshold_gpu(
+        &self,
+        img: &GpuImage<Luma<u8>>,
+        threshold: u8,
+    ) -> Result<GpuImage<Luma<u8>>, ImgprocGpuError> {
+        let dest = self.alloc_gpu_like(img)?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("threshold")
+            .queue(self.queue.clone())
+            .global_work_size(img.dimensions())
+            .arg(img.as_ocl_image())
+            .arg(dest.as_ocl_image())
+            .arg(&(threshold as u32))
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "threshold",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "threshold",
+                    source,
+                })?;
+        }
+
+        Ok(dest)
+    }
+
+    /// Device-resident, in-place counterpart of
+    /// [`threshold_mut`](Executor::threshold_mut).
+    pub fn threshold_gpu_mut(
+        &self,
+        img: &GpuImage<Luma<u8>>,
+        threshold: u8,
+    ) -> Result<(), ImgprocGpuError> {
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("threshold_mut")
+            .queue(self.queue.clone())
+            .global_work_size(img.dimensions())
+            .arg(img.as_ocl_image())
+            .arg(&(threshold as u32))
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "threshold_mut",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "threshold_mut",
+                    source,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Device-resident counterpart of
+    /// [`adaptive_threshold`](Executor::adaptive_threshold).
+    pub fn adaptive_threshold_gpu(
+        &self,
+        img: &GpuImage<Luma<u8>>,
+        block_radius: u32,
+    ) -> Result<GpuImage<Luma<u8>>, ImgprocGpuError> {
+        assert!(block_radius > 0);
+
+        let dest = self.alloc_gpu_like(img)?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("adaptive_threshold")
+            .queue(self.queue.clone())
+            .global_work_size(img.dimensions())
+            .arg(img.as_ocl_image())
+            .arg(dest.as_ocl_image())
+            .arg(&(block_radius as i32))
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "adaptive_threshold",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "adaptive_threshold",
+                    source,
+                })?;
+        }
+
+        Ok(dest)
+    }
+
+    /// Device-resident counterpart of
+    /// [`stretch_contrast`](Executor::stretch_contrast).
+    pub fn stretch_contrast_gpu(
+        &self,
+        img: &GpuImage<Luma<u8>>,
+        lower: u8,
+        upper: u8,
+    ) -> Result<GpuImage<Luma<u8>>, ImgprocGpuError> {
+        assert!(upper > lower, "upper must be strictly greater than lower");
+
+        let dest = self.alloc_gpu_like(img)?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("stretch_contrast")
+            .queue(self.queue.clone())
+            .global_work_size(img.dimensions())
+            .arg(img.as_ocl_image())
+            .arg(dest.as_ocl_image())
+            .arg(&(lower as u32))
+            .arg(&(upper as u32))
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "stretch_contrast",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "stretch_contrast",
+                    source,
+                })?;
+        }
+
+        Ok(dest)
+    }
+
+    /// ROI variant of [`threshold`](Executor::threshold): only the
+    /// `region`-sized rectangle starting at `origin` is cropped out on the
+    /// host, uploaded, thresholded and read back, so a large `img` never
+    /// pays for uploading/processing more than that rectangle.
+    pub fn threshold_region(
+        &self,
+        img: &GrayImage,
+        threshold: u8,
+        origin: (u32, u32),
+        region: (u32, u32),
+    ) -> Result<GrayImage, ImgprocGpuError> {
+        validate_region(img.dimensions(), origin, region)?;
+
+        let roi = image::imageops::crop_imm(img, origin.0, origin.1, region.0, region.1).to_image();
+
+        let src = self.alloc_img(
+            &roi,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let dest = self.alloc_img(
+            &roi,
+            Some(
+                ocl::flags::MEM_WRITE_ONLY
+                    | ocl::flags::MEM_HOST_READ_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("threshold_region")
+            .queue(self.queue.clone())
+            .global_work_size(&region)
+            .arg(&src)
+            .arg(&dest)
+            .arg(&(threshold as u32))
+            .arg(&0i32)
+            .arg(&0i32)
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "threshold_region",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "threshold_region",
+                    source,
+                })?;
+        }
+
+        read_region(&dest, (0, 0), region)
+    }
+
+    pub fn threshold(&self, img: &GrayImage, threshold: u8) -> Result<GrayImage, ImgprocGpuError> {
         let src = self.alloc_img(
             img,
             Some(
@@ -25,7 +230,7 @@ impl Executor {
                     | ocl::flags::MEM_HOST_WRITE_ONLY
                     | ocl::flags::MEM_COPY_HOST_PTR,
             ),
-        );
+        )?;
 
         let dest = self.alloc_img(
             img,
@@ -34,12 +239,12 @@ impl Executor {
                     | ocl::flags::MEM_HOST_READ_ONLY
                     | ocl::flags::MEM_COPY_HOST_PTR,
             ),
-        );
+        )?;
 
         let dims = img.dimensions();
 
         let kernel = ocl::Kernel::builder()
-            .program(self.get_program(&Feature::Contrast))
+            .program(self.get_program(&Feature::Contrast)?)
             .name("threshold")
             .queue(self.queue.clone())
             .global_work_size(&dims)
@@ -47,45 +252,64 @@ impl Executor {
             .arg(&dest)
             .arg(&(threshold as u32))
             .build()
-            .expect("threshold kernel could not be loaded!");
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "threshold",
+                source,
+            })?;
 
         unsafe {
-            kernel.enq().expect("Error while enqueueing the kernel!");
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "threshold",
+                    source,
+                })?;
         }
 
         let mut output = image::ImageBuffer::new(dims.0, dims.1);
 
         dest.read(&mut output)
             .enq()
-            .expect("Error while copying device mem to host!");
+            .map_err(ImgprocGpuError::MemRead)?;
 
-        output
+        Ok(output)
     }
 
-    pub fn threshold_mut(&self, img: &mut GrayImage, threshold: u8) {
-        let output = self.alloc_img(&img, None);
+    pub fn threshold_mut(&self, img: &mut GrayImage, threshold: u8) -> Result<(), ImgprocGpuError> {
+        let output = self.alloc_img(&img, None)?;
 
         let kernel = ocl::Kernel::builder()
-            .program(self.get_program(&Feature::Contrast))
+            .program(self.get_program(&Feature::Contrast)?)
             .name("threshold_mut")
             .queue(self.queue.clone())
             .global_work_size(&img.dimensions())
             .arg(&output)
             .arg(&(threshold as u32))
             .build()
-            .expect("threshold_mut kernel could not be loaded!");
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "threshold_mut",
+                source,
+            })?;
 
         unsafe {
-            kernel.enq().expect("Error while enqueueing the kernel!");
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "threshold_mut",
+                    source,
+                })?;
         }
 
-        output
-            .read(img)
-            .enq()
-            .expect("Error while copying device mem to host!");
+        output.read(img).enq().map_err(ImgprocGpuError::MemRead)?;
+
+        Ok(())
     }
 
-    pub fn adaptive_threshold(&self, img: &GrayImage, block_radius: u32) -> GrayImage {
+    pub fn adaptive_threshold(
+        &self,
+        img: &GrayImage,
+        block_radius: u32,
+    ) -> Result<GrayImage, ImgprocGpuError> {
         assert!(block_radius > 0);
 
         let src = self.alloc_img(
@@ -95,7 +319,7 @@ impl Executor {
                     | ocl::flags::MEM_HOST_WRITE_ONLY
                     | ocl::flags::MEM_COPY_HOST_PTR,
             ),
-        );
+        )?;
 
         let dest = self.alloc_img(
             img,
@@ -104,12 +328,12 @@ impl Executor {
                     | ocl::flags::MEM_HOST_READ_ONLY
                     | ocl::flags::MEM_COPY_HOST_PTR,
             ),
-        );
+        )?;
 
         let dims = img.dimensions();
 
         let kernel = ocl::Kernel::builder()
-            .program(self.get_program(&Feature::Contrast))
+            .program(self.get_program(&Feature::Contrast)?)
             .name("adaptive_threshold")
             .queue(self.queue.clone())
             .global_work_size(&dims)
@@ -117,22 +341,102 @@ impl Executor {
             .arg(&dest)
             .arg(&(block_radius as i32))
             .build()
-            .expect("adaptive_threshold kernel could not be loaded!");
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "adaptive_threshold",
+                source,
+            })?;
 
         unsafe {
-            kernel.enq().expect("Error while enqueueing the kernel!");
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "adaptive_threshold",
+                    source,
+                })?;
         }
 
         let mut output = image::ImageBuffer::new(dims.0, dims.1);
 
         dest.read(&mut output)
             .enq()
-            .expect("Error while copying device mem to host!");
+            .map_err(ImgprocGpuError::MemRead)?;
 
-        output
+        Ok(output)
     }
 
-    pub fn stretch_contrast(&self, img: &GrayImage, lower: u8, upper: u8) -> GrayImage {
+    /// ROI variant of [`adaptive_threshold`](Executor::adaptive_threshold):
+    /// only the `region`-sized rectangle starting at `origin` is cropped out
+    /// on the host, uploaded, thresholded and read back, so a large `img`
+    /// never pays for uploading/processing more than that rectangle.
+    /// `block_radius` windows that would extend past the ROI are clamped to
+    /// the ROI edges rather than the whole image's, to match what a caller
+    /// who only uploaded/cares about this rectangle would expect.
+    pub fn adaptive_threshold_region(
+        &self,
+        img: &GrayImage,
+        block_radius: u32,
+        origin: (u32, u32),
+        region: (u32, u32),
+    ) -> Result<GrayImage, ImgprocGpuError> {
+        assert!(block_radius > 0);
+        validate_region(img.dimensions(), origin, region)?;
+
+        let roi = image::imageops::crop_imm(img, origin.0, origin.1, region.0, region.1).to_image();
+
+        let src = self.alloc_img(
+            &roi,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let dest = self.alloc_img(
+            &roi,
+            Some(
+                ocl::flags::MEM_WRITE_ONLY
+                    | ocl::flags::MEM_HOST_READ_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("adaptive_threshold_region")
+            .queue(self.queue.clone())
+            .global_work_size(&region)
+            .arg(&src)
+            .arg(&dest)
+            .arg(&(block_radius as i32))
+            .arg(&0i32)
+            .arg(&0i32)
+            .arg(&(region.0 as i32))
+            .arg(&(region.1 as i32))
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "adaptive_threshold_region",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "adaptive_threshold_region",
+                    source,
+                })?;
+        }
+
+        read_region(&dest, (0, 0), region)
+    }
+
+    pub fn stretch_contrast(
+        &self,
+        img: &GrayImage,
+        lower: u8,
+        upper: u8,
+    ) -> Result<GrayImage, ImgprocGpuError> {
         assert!(upper > lower, "upper must be strictly greater than lower");
 
         let src = self.alloc_img(
@@ -142,7 +446,7 @@ impl Executor {
                     | ocl::flags::MEM_HOST_WRITE_ONLY
                     | ocl::flags::MEM_COPY_HOST_PTR,
             ),
-        );
+        )?;
 
         let dest = self.alloc_img(
             img,
@@ -151,12 +455,12 @@ impl Executor {
                     | ocl::flags::MEM_HOST_READ_ONLY
                     | ocl::flags::MEM_COPY_HOST_PTR,
             ),
-        );
+        )?;
 
         let dims = img.dimensions();
 
         let kernel = ocl::Kernel::builder()
-            .program(self.get_program(&Feature::Contrast))
+            .program(self.get_program(&Feature::Contrast)?)
             .name("stretch_contrast")
             .queue(self.queue.clone())
             .global_work_size(&dims)
@@ -165,22 +469,274 @@ impl Executor {
             .arg(&(lower as u32))
             .arg(&(upper as u32))
             .build()
-            .expect("stretch_contrast kernel could not be loaded!");
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "stretch_contrast",
+                source,
+            })?;
 
         unsafe {
-            kernel.enq().expect("Error while enqueueing the kernel!");
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "stretch_contrast",
+                    source,
+                })?;
         }
 
         let mut output = image::ImageBuffer::new(dims.0, dims.1);
 
         dest.read(&mut output)
             .enq()
-            .expect("Error while copying device mem to host!");
+            .map_err(ImgprocGpuError::MemRead)?;
+
+        Ok(output)
+    }
+
+    /// Computes a 256-bin grayscale histogram on the device, using
+    /// local-memory sub-histograms per work-group to keep global atomic
+    /// contention down.
+    pub fn histogram(&self, img: &GrayImage) -> Result<[u32; 256], ImgprocGpuError> {
+        let src = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let histogram_buffer = ocl::Buffer::<u32>::builder()
+            .queue(self.queue.clone())
+            .len(256)
+            .fill_val(0u32)
+            .build()
+            .map_err(ImgprocGpuError::BufferAlloc)?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("histogram")
+            .queue(self.queue.clone())
+            .global_work_size(&img.dimensions())
+            .arg(&src)
+            .arg(&histogram_buffer)
+            .arg_local::<u32>(256)
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "histogram",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "histogram",
+                    source,
+                })?;
+        }
+
+        let mut histogram = [0u32; 256];
+        histogram_buffer
+            .read(histogram.as_mut_slice())
+            .enq()
+            .map_err(ImgprocGpuError::MemRead)?;
 
-        output
+        Ok(histogram)
+    }
+
+    /// Finds the threshold maximizing between-class variance (Otsu's
+    /// method), from a histogram computed on the device.
+    pub fn otsu_threshold(&self, img: &GrayImage) -> Result<u8, ImgprocGpuError> {
+        Ok(otsu_threshold_from_histogram(&self.histogram(img)?))
+    }
+
+    /// Stretches `img`'s intensity distribution to use the full 0-255 range
+    /// by applying a lookup table built from its cumulative distribution.
+    pub fn equalize_histogram(&self, img: &GrayImage) -> Result<GrayImage, ImgprocGpuError> {
+        let lut = equalization_lut(&self.histogram(img)?);
+
+        let src = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let dest = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_WRITE_ONLY
+                    | ocl::flags::MEM_HOST_READ_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let lut_buffer = ocl::Buffer::<u8>::builder()
+            .queue(self.queue.clone())
+            .len(256)
+            .copy_host_slice(&lut)
+            .build()
+            .map_err(ImgprocGpuError::BufferAlloc)?;
+
+        let dims = img.dimensions();
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Contrast)?)
+            .name("apply_lut")
+            .queue(self.queue.clone())
+            .global_work_size(&dims)
+            .arg(&src)
+            .arg(&dest)
+            .arg(&lut_buffer)
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "apply_lut",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "apply_lut",
+                    source,
+                })?;
+        }
+
+        let mut output = image::ImageBuffer::new(dims.0, dims.1);
+
+        dest.read(&mut output)
+            .enq()
+            .map_err(ImgprocGpuError::MemRead)?;
+
+        Ok(output)
     }
 }
 
+/// Finds `t` in `0..256` maximizing the between-class variance
+/// `w0 * w1 * (mean0 - mean1)^2`, over the class split induced by `t`.
+fn otsu_threshold_from_histogram(histogram: &[u32; 256]) -> u8 {
+    let total: u64 = histogram.iter().map(|&c| c as u64).sum();
+    if total == 0 {
+        return 0;
+    }
+
+    let sum_all: u64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| i as u64 * c as u64)
+        .sum();
+
+    let mut weight_background = 0u64;
+    let mut sum_background = 0u64;
+    let mut best_threshold = 0u8;
+    let mut best_variance = 0.0f64;
+
+    for (t, &count) in histogram.iter().enumerate() {
+        weight_background += count as u64;
+        if weight_background == 0 {
+            continue;
+        }
+
+        let weight_foreground = total - weight_background;
+        if weight_foreground == 0 {
+            break;
+        }
+
+        sum_background += t as u64 * count as u64;
+
+        let mean_background = sum_background as f64 / weight_background as f64;
+        let mean_foreground = (sum_all - sum_background) as f64 / weight_foreground as f64;
+
+        let between_class_variance = weight_background as f64
+            * weight_foreground as f64
+            * (mean_background - mean_foreground).powi(2);
+
+        if between_class_variance > best_variance {
+            best_variance = between_class_variance;
+            best_threshold = t as u8;
+        }
+    }
+
+    // `best_threshold` is the last level scored into the background
+    // (background = 0..=best_threshold); the returned `t` is the first level
+    // of the foreground, so the split is `i < t` against `i >= t`.
+    best_threshold.saturating_add(1)
+}
+
+/// Builds the 256-entry lookup table mapping each level to
+/// `round(255 * (cdf - cdf_min) / (total - cdf_min))`.
+fn equalization_lut(histogram: &[u32; 256]) -> [u8; 256] {
+    let total: u64 = histogram.iter().map(|&c| c as u64).sum();
+    let cdf_min = histogram
+        .iter()
+        .find(|&&count| count > 0)
+        .copied()
+        .unwrap_or(0) as u64;
+
+    let mut lut = [0u8; 256];
+    let mut cumulative = 0u64;
+
+    for (i, &count) in histogram.iter().enumerate() {
+        cumulative += count as u64;
+
+        lut[i] = if total <= cdf_min {
+            0
+        } else {
+            let value = (cumulative - cdf_min) as f64 / (total - cdf_min) as f64 * 255.0;
+            value.round().clamp(0.0, 255.0) as u8
+        };
+    }
+
+    lut
+}
+
+/// Checks that `origin + region` stays within `dims`. Writes aren't
+/// sampler-clamped the way reads are, so dispatching a ROI kernel on an
+/// out-of-range pair would write past the allocated device image instead of
+/// cleanly failing.
+fn validate_region(
+    dims: (u32, u32),
+    origin: (u32, u32),
+    region: (u32, u32),
+) -> Result<(), ImgprocGpuError> {
+    let fits = origin.0.checked_add(region.0).map_or(false, |x| x <= dims.0)
+        && origin.1.checked_add(region.1).map_or(false, |y| y <= dims.1);
+
+    if fits {
+        Ok(())
+    } else {
+        Err(ImgprocGpuError::RegionOutOfBounds {
+            origin,
+            region,
+            dims,
+        })
+    }
+}
+
+/// Reads `region` pixels starting at `origin` out of `dest`, into a freshly
+/// allocated, tightly packed `region`-sized buffer.
+fn read_region(
+    dest: &ocl::Image<u8>,
+    origin: (u32, u32),
+    region: (u32, u32),
+) -> Result<GrayImage, ImgprocGpuError> {
+    let mut output = image::ImageBuffer::new(region.0, region.1);
+
+    // `output` is tightly packed at `region`'s size, not `dest`'s full
+    // width, so row_pitch must default (0 = `region.0 * pixel size`) rather
+    // than being set to the source image's pitch.
+    dest.read(&mut output)
+        .origin((origin.0 as usize, origin.1 as usize, 0))
+        .region((region.0 as usize, region.1 as usize, 1))
+        .enq()
+        .map_err(ImgprocGpuError::MemRead)?;
+
+    Ok(output)
+}
+
 #[cfg(test)]
 mod tests {
     use image::Luma;
@@ -193,7 +749,7 @@ mod tests {
         let executor = Executor::default();
 
         let image = GrayImage::from_pixel(3, 3, Luma([100u8]));
-        let binary = executor.adaptive_threshold(&image, 1);
+        let binary = executor.adaptive_threshold(&image, 1).unwrap();
         let expected = GrayImage::from_pixel(3, 3, Luma([255u8]));
         assert_pixels_eq!(binary, expected);
     }
@@ -206,7 +762,7 @@ mod tests {
             for x in 0..3 {
                 let mut image = GrayImage::from_pixel(3, 3, Luma([200u8]));
                 image.put_pixel(x, y, Luma([100u8]));
-                let binary = executor.adaptive_threshold(&image, 1);
+                let binary = executor.adaptive_threshold(&image, 1).unwrap();
                 // All except the dark pixel have brightness >= their local mean
                 let mut expected = GrayImage::from_pixel(3, 3, Luma([255u8]));
                 expected.put_pixel(x, y, Luma([0u8]));
@@ -224,7 +780,7 @@ mod tests {
                 let mut image = GrayImage::from_pixel(5, 5, Luma([100u8]));
                 image.put_pixel(x, y, Luma([200u8]));
 
-                let binary = executor.adaptive_threshold(&image, 1);
+                let binary = executor.adaptive_threshold(&image, 1).unwrap();
 
                 for yb in 0..5 {
                     for xb in 0..5 {
@@ -257,7 +813,7 @@ mod tests {
         let executor = Executor::default();
 
         let expected = 0u8;
-        let actual = executor.threshold(&constant_image(10, 10, 0), 0);
+        let actual = executor.threshold(&constant_image(10, 10, 0), 0).unwrap();
         assert_pixels_eq!(actual, constant_image(10, 10, expected));
     }
 
@@ -266,7 +822,7 @@ mod tests {
         let executor = Executor::default();
 
         let expected = 255u8;
-        let actual = executor.threshold(&constant_image(10, 10, 1), 0);
+        let actual = executor.threshold(&constant_image(10, 10, 1), 0).unwrap();
         assert_pixels_eq!(actual, constant_image(10, 10, expected));
     }
 
@@ -275,7 +831,9 @@ mod tests {
         let executor = Executor::default();
 
         let expected = 0u8;
-        let actual = executor.threshold(&constant_image(10, 10, 255), 255);
+        let actual = executor
+            .threshold(&constant_image(10, 10, 255), 255)
+            .unwrap();
         assert_pixels_eq!(actual, constant_image(10, 10, expected));
     }
 
@@ -290,7 +848,174 @@ mod tests {
 
         let expected = GrayImage::from_raw(26, 1, expected_contents).unwrap();
 
-        let actual = executor.threshold(&original, 125u8);
+        let actual = executor.threshold(&original, 125u8).unwrap();
         assert_pixels_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_threshold_region_matches_full_threshold_subrect() {
+        let executor = Executor::default();
+
+        let original_contents = (0u8..26u8).map(|x| x * 10u8).collect();
+        let original = GrayImage::from_raw(26, 1, original_contents).unwrap();
+
+        let full = executor.threshold(&original, 125u8).unwrap();
+        let roi = executor
+            .threshold_region(&original, 125u8, (10, 0), (6, 1))
+            .unwrap();
+
+        let expected = image::imageops::crop_imm(&full, 10, 0, 6, 1).to_image();
+        assert_pixels_eq!(expected, roi);
+    }
+
+    #[test]
+    fn test_threshold_region_matches_full_threshold_multi_row_subrect() {
+        let executor = Executor::default();
+
+        // A multi-row, non-full-width ROI: `region.0 != dims.0` and
+        // `region.1 > 1` together are what exposed the previous row_pitch
+        // bug, since a single-row ROI can't stride past its own buffer.
+        let original_contents: Vec<u8> = (0u8..100u8).collect();
+        let original = GrayImage::from_raw(10, 10, original_contents).unwrap();
+
+        let full = executor.threshold(&original, 50u8).unwrap();
+        let roi = executor
+            .threshold_region(&original, 50u8, (2, 3), (5, 4))
+            .unwrap();
+
+        let expected = image::imageops::crop_imm(&full, 2, 3, 5, 4).to_image();
+        assert_pixels_eq!(expected, roi);
+    }
+
+    #[test]
+    fn test_adaptive_threshold_region_constant_multi_row() {
+        let executor = Executor::default();
+
+        // A non-full-width, multi-row ROI on a constant image: every pixel
+        // equals its own local mean, so the result is unambiguous regardless
+        // of how the block window clamps at the ROI's edges, while still
+        // exercising the row_pitch read-back path region-based kernels share.
+        let image = GrayImage::from_pixel(10, 10, Luma([100u8]));
+        let roi = executor
+            .adaptive_threshold_region(&image, 1, (2, 3), (5, 4))
+            .unwrap();
+
+        let expected = GrayImage::from_pixel(5, 4, Luma([255u8]));
+        assert_pixels_eq!(expected, roi);
+    }
+
+    #[test]
+    fn test_threshold_region_rejects_out_of_bounds_region() {
+        let executor = Executor::default();
+        let image = constant_image(10, 10, 0);
+
+        let err = executor
+            .threshold_region(&image, 125u8, (6, 0), (5, 1))
+            .unwrap_err();
+        assert!(matches!(err, ImgprocGpuError::RegionOutOfBounds { .. }));
+    }
+
+    #[test]
+    fn test_adaptive_threshold_region_rejects_out_of_bounds_region() {
+        let executor = Executor::default();
+        let image = constant_image(10, 10, 100);
+
+        let err = executor
+            .adaptive_threshold_region(&image, 1, (0, 6), (1, 5))
+            .unwrap_err();
+        assert!(matches!(err, ImgprocGpuError::RegionOutOfBounds { .. }));
+    }
+
+    #[test]
+    fn test_threshold_gpu_matches_host_round_trip() {
+        let executor = Executor::default();
+
+        let original_contents = (0u8..26u8).map(|x| x * 10u8).collect();
+        let original = GrayImage::from_raw(26, 1, original_contents).unwrap();
+
+        let gpu_image = executor.upload(&original).unwrap();
+        let actual = executor
+            .threshold_gpu(&gpu_image, 125u8)
+            .unwrap()
+            .download()
+            .unwrap();
+
+        let expected = executor.threshold(&original, 125u8).unwrap();
+        assert_pixels_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_threshold_gpu_mut_matches_host_round_trip() {
+        let executor = Executor::default();
+
+        let original_contents = (0u8..26u8).map(|x| x * 10u8).collect();
+        let original = GrayImage::from_raw(26, 1, original_contents).unwrap();
+
+        let gpu_image = executor.upload(&original).unwrap();
+        executor.threshold_gpu_mut(&gpu_image, 125u8).unwrap();
+        let actual = gpu_image.download().unwrap();
+
+        let expected = executor.threshold(&original, 125u8).unwrap();
+        assert_pixels_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_gpu_chain_stretch_then_adaptive_threshold_matches_host_round_trip() {
+        let executor = Executor::default();
+
+        let original_contents = (0u8..25u8).map(|x| x * 10u8).collect();
+        let original = GrayImage::from_raw(5, 5, original_contents).unwrap();
+
+        // Chains two `_gpu` ops with no host round-trip between them, to
+        // prove the resident-buffer pipeline (not just a single op
+        // upload/download round-trip) actually works.
+        let gpu_image = executor.upload(&original).unwrap();
+        let actual = executor
+            .stretch_contrast_gpu(&gpu_image, 50, 200)
+            .unwrap();
+        let actual = executor
+            .adaptive_threshold_gpu(&actual, 1)
+            .unwrap()
+            .download()
+            .unwrap();
+
+        let stretched = executor.stretch_contrast(&original, 50, 200).unwrap();
+        let expected = executor.adaptive_threshold(&stretched, 1).unwrap();
+
+        assert_pixels_eq!(expected, actual);
+    }
+
+    #[test]
+    fn otsu_threshold_separates_two_clusters() {
+        let mut histogram = [0u32; 256];
+        histogram[10] = 100;
+        histogram[200] = 100;
+
+        let threshold = otsu_threshold_from_histogram(&histogram);
+        assert!(threshold > 10 && threshold < 200);
+    }
+
+    #[test]
+    fn equalization_lut_is_identity_for_already_full_range() {
+        let mut histogram = [0u32; 256];
+        histogram[0] = 1;
+        histogram[255] = 1;
+
+        let lut = equalization_lut(&histogram);
+        assert_eq!(lut[0], 0);
+        assert_eq!(lut[255], 255);
+    }
+
+    #[test]
+    fn equalization_lut_is_nondecreasing() {
+        let mut histogram = [0u32; 256];
+        histogram[0] = 5;
+        histogram[64] = 10;
+        histogram[200] = 3;
+
+        let lut = equalization_lut(&histogram);
+        for w in lut.windows(2) {
+            assert!(w[1] >= w[0]);
+        }
+    }
 }
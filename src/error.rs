@@ -0,0 +1,138 @@
+// Copyright 2021 Jerónimo Sánchez <jeronimosg@hotmail.es>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//   http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+/// Everything that can go wrong setting up or driving the OpenCL pipeline,
+/// returned instead of panicking so callers (e.g. on a headless/CI machine
+/// with no OpenCL device) can fall back to a CPU path.
+#[derive(Debug)]
+pub enum ImgprocGpuError {
+    /// `ocl::Platform::list()` returned no platforms.
+    NoPlatform,
+    /// No device was found for the chosen platform.
+    NoDevice,
+    /// Building the OpenCL context failed.
+    ContextBuild(ocl::Error),
+    /// Creating the command queue failed.
+    QueueBuild(ocl::Error),
+    /// Compiling a feature's `.cl` program failed.
+    ProgramBuild {
+        feature: &'static str,
+        source: ocl::Error,
+    },
+    /// An operation needed a feature whose program was never built, because
+    /// the crate was compiled without that feature's cargo flag.
+    FeatureNotEnabled(&'static str),
+    /// `alloc_img` was given an `image::ColorType` with no corresponding
+    /// OpenCL channel order/data type.
+    UnsupportedColorType(image::ColorType),
+    /// Allocating a device image failed.
+    ImageAlloc(ocl::Error),
+    /// Allocating a device buffer failed.
+    BufferAlloc(ocl::Error),
+    /// Building a kernel failed.
+    KernelBuild {
+        name: &'static str,
+        source: ocl::Error,
+    },
+    /// Enqueueing a kernel failed.
+    KernelEnqueue {
+        name: &'static str,
+        source: ocl::Error,
+    },
+    /// Reading device memory back to the host failed.
+    MemRead(ocl::Error),
+    /// An `origin`/`region` pair passed to a ROI operation extends past the
+    /// source image's dimensions.
+    RegionOutOfBounds {
+        origin: (u32, u32),
+        region: (u32, u32),
+        dims: (u32, u32),
+    },
+}
+
+impl fmt::Display for ImgprocGpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImgprocGpuError::NoPlatform => write!(f, "there are no available OpenCL platforms"),
+            ImgprocGpuError::NoDevice => {
+                write!(f, "there are no devices for this OpenCL platform")
+            }
+            ImgprocGpuError::ContextBuild(source) => {
+                write!(f, "could not build the OpenCL context: {}", source)
+            }
+            ImgprocGpuError::QueueBuild(source) => {
+                write!(f, "could not create the command queue: {}", source)
+            }
+            ImgprocGpuError::ProgramBuild { feature, source } => {
+                write!(f, "could not build the {} program: {}", feature, source)
+            }
+            ImgprocGpuError::FeatureNotEnabled(feature) => write!(
+                f,
+                "the {} feature is not enabled/initialized (is its cargo feature on?)",
+                feature
+            ),
+            ImgprocGpuError::UnsupportedColorType(color_type) => {
+                write!(f, "unsupported color type: {:?}", color_type)
+            }
+            ImgprocGpuError::ImageAlloc(source) => {
+                write!(f, "could not allocate image on GPU: {}", source)
+            }
+            ImgprocGpuError::BufferAlloc(source) => {
+                write!(f, "could not allocate buffer on GPU: {}", source)
+            }
+            ImgprocGpuError::KernelBuild { name, source } => {
+                write!(f, "{} kernel could not be loaded: {}", name, source)
+            }
+            ImgprocGpuError::KernelEnqueue { name, source } => write!(
+                f,
+                "error while enqueueing the {} kernel: {}",
+                name, source
+            ),
+            ImgprocGpuError::MemRead(source) => {
+                write!(f, "error while copying device mem to host: {}", source)
+            }
+            ImgprocGpuError::RegionOutOfBounds {
+                origin,
+                region,
+                dims,
+            } => write!(
+                f,
+                "region {:?} at origin {:?} exceeds image dimensions {:?}",
+                region, origin, dims
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImgprocGpuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImgprocGpuError::ContextBuild(source)
+            | ImgprocGpuError::QueueBuild(source)
+            | ImgprocGpuError::ProgramBuild { source, .. }
+            | ImgprocGpuError::ImageAlloc(source)
+            | ImgprocGpuError::BufferAlloc(source)
+            | ImgprocGpuError::KernelBuild { source, .. }
+            | ImgprocGpuError::KernelEnqueue { source, .. }
+            | ImgprocGpuError::MemRead(source) => Some(source),
+            ImgprocGpuError::NoPlatform
+            | ImgprocGpuError::NoDevice
+            | ImgprocGpuError::FeatureNotEnabled(_)
+            | ImgprocGpuError::UnsupportedColorType(_)
+            | ImgprocGpuError::RegionOutOfBounds { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2021 Jerónimo Sánchez <jeronimosg@hotmail.es>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//   http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ocl::enums::{ImageChannelDataType, ImageChannelOrder};
+use std::marker::PhantomData;
+
+use crate::ImgprocGpuError;
+
+/// An image that stays resident on the device across a chain of `_gpu`
+/// operations, avoiding the host round-trip that a plain `Executor` call
+/// (e.g. [`threshold`](crate::Executor::threshold)) pays on every step.
+///
+/// Obtain one with [`Executor::upload`](crate::Executor::upload) and bring it
+/// back to the host with [`GpuImage::download`].
+pub struct GpuImage<P: image::Pixel> {
+    img: ocl::Image<P::Subpixel>,
+    dims: (u32, u32),
+    order: ImageChannelOrder,
+    c_type: ImageChannelDataType,
+    _pixel: PhantomData<P>,
+}
+
+impl<P> GpuImage<P>
+where
+    P: image::Pixel + 'static,
+    P::Subpixel: ocl::traits::OclPrm + 'static,
+{
+    pub(crate) fn new(
+        img: ocl::Image<P::Subpixel>,
+        dims: (u32, u32),
+        order: ImageChannelOrder,
+        c_type: ImageChannelDataType,
+    ) -> Self {
+        Self {
+            img,
+            dims,
+            order,
+            c_type,
+            _pixel: PhantomData,
+        }
+    }
+
+    pub(crate) fn as_ocl_image(&self) -> &ocl::Image<P::Subpixel> {
+        &self.img
+    }
+
+    pub(crate) fn order(&self) -> ImageChannelOrder {
+        self.order
+    }
+
+    pub(crate) fn c_type(&self) -> ImageChannelDataType {
+        self.c_type
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dims
+    }
+
+    /// Copies the whole image back to the host.
+    pub fn download(&self) -> Result<image::ImageBuffer<P, Vec<P::Subpixel>>, ImgprocGpuError> {
+        self.download_region(None, None)
+    }
+
+    /// Copies a sub-rectangle of the image back to the host, as a
+    /// `(region.0, region.1)`-sized buffer starting at `origin`. Passing
+    /// `None` for either defaults to the whole image, mirroring the
+    /// origin/region pair `cl_enqueue_read_image` takes.
+    pub fn download_region(
+        &self,
+        origin: Option<(u32, u32)>,
+        region: Option<(u32, u32)>,
+    ) -> Result<image::ImageBuffer<P, Vec<P::Subpixel>>, ImgprocGpuError> {
+        let origin = origin.unwrap_or((0, 0));
+        let region = region.unwrap_or(self.dims);
+
+        let mut output = image::ImageBuffer::new(region.0, region.1);
+
+        self.img
+            .read(&mut output)
+            .origin((origin.0 as usize, origin.1 as usize, 0))
+            .region((region.0 as usize, region.1 as usize, 1))
+            .enq()
+            .map_err(ImgprocGpuError::MemRead)?;
+
+        Ok(output)
+    }
+}
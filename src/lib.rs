@@ -13,16 +13,43 @@
 // limitations under the License.
 
 use log::info;
-use ocl::Device;
+use ocl::{
+    core::MemObjectType,
+    enums::{ImageChannelDataType, ImageChannelOrder},
+    Device,
+};
 use std::collections::HashMap;
 
+pub mod color_correct;
 pub mod contrast;
+pub mod debayer;
+mod error;
 pub mod geometric_trans;
+mod gpu_image;
+pub mod quantize;
+
+pub use error::ImgprocGpuError;
+pub use gpu_image::GpuImage;
 
 #[derive(PartialEq, Eq, Hash)]
 pub(crate) enum Feature {
+    ColorCorrect,
     Contrast,
+    Debayer,
     Geometric_Trans,
+    Quantize,
+}
+
+impl Feature {
+    fn name(&self) -> &'static str {
+        match self {
+            Feature::ColorCorrect => "color_correct",
+            Feature::Contrast => "contrast",
+            Feature::Debayer => "debayer",
+            Feature::Geometric_Trans => "geometric_trans",
+            Feature::Quantize => "quantize",
+        }
+    }
 }
 
 pub struct Executor {
@@ -31,30 +58,41 @@ pub struct Executor {
 }
 
 impl Default for Executor {
+    /// Convenience constructor for the common case of a single available
+    /// device. Panics where [`Executor::try_default`] would return an
+    /// [`ImgprocGpuError`] — prefer `try_default` on a machine that might
+    /// not have an OpenCL device (e.g. headless CI).
     fn default() -> Self {
+        Executor::try_default().expect("Could not create a default Executor")
+    }
+}
+
+impl Executor {
+    /// Fallible counterpart of [`Executor::default`]: picks the last listed
+    /// platform and its first device, without panicking if none is found.
+    pub fn try_default() -> Result<Self, ImgprocGpuError> {
         let platform = ocl::Platform::list()
             .pop()
-            .expect("There are no available platforms!");
-        let device = Device::first(platform).expect("There are no devices for this platform!");
+            .ok_or(ImgprocGpuError::NoPlatform)?;
+        let device = Device::first(platform).map_err(|_| ImgprocGpuError::NoDevice)?;
 
         info!(
             "Using {} - {}",
-            platform.name().unwrap(),
-            device.name().unwrap()
+            platform.name().unwrap_or_default(),
+            device.name().unwrap_or_default()
         );
 
         Executor::new(device)
     }
-}
 
-impl Executor {
-    pub fn new(device: Device) -> Self {
+    pub fn new(device: Device) -> Result<Self, ImgprocGpuError> {
         let context = ocl::Context::builder()
             .devices(device)
             .build()
-            .expect("Could not build the context!");
+            .map_err(ImgprocGpuError::ContextBuild)?;
 
-        let queue = ocl::Queue::new(&context, device, None).expect("Could not create the queue!");
+        let queue =
+            ocl::Queue::new(&context, device, None).map_err(ImgprocGpuError::QueueBuild)?;
         let mut programs = HashMap::new();
 
         //Create progams for each feature
@@ -64,7 +102,10 @@ impl Executor {
                 .devices(device)
                 .src_file("programs/contrast.cl")
                 .build(&context)
-                .expect("Could not build the contrast program!");
+                .map_err(|source| ImgprocGpuError::ProgramBuild {
+                    feature: Feature::Contrast.name(),
+                    source,
+                })?;
 
             programs.insert(Feature::Contrast, contrast);
 
@@ -77,73 +118,89 @@ impl Executor {
                 .devices(device)
                 .src_file("programs/geometric_trans.cl")
                 .build(&context)
-                .expect("Could not build the geometric transformations program!");
+                .map_err(|source| ImgprocGpuError::ProgramBuild {
+                    feature: Feature::Geometric_Trans.name(),
+                    source,
+                })?;
 
             programs.insert(Feature::Geometric_Trans, geometric);
 
             info!("Added geometric transformations feature");
         }
 
-        Self { queue, programs }
+        #[cfg(feature = "color_correct")]
+        {
+            let color_correct = ocl::Program::builder()
+                .devices(device)
+                .src_file("programs/color_correct.cl")
+                .build(&context)
+                .map_err(|source| ImgprocGpuError::ProgramBuild {
+                    feature: Feature::ColorCorrect.name(),
+                    source,
+                })?;
+
+            programs.insert(Feature::ColorCorrect, color_correct);
+
+            info!("Added color correction feature");
+        }
+
+        #[cfg(feature = "debayer")]
+        {
+            let debayer = ocl::Program::builder()
+                .devices(device)
+                .src_file("programs/debayer.cl")
+                .build(&context)
+                .map_err(|source| ImgprocGpuError::ProgramBuild {
+                    feature: Feature::Debayer.name(),
+                    source,
+                })?;
+
+            programs.insert(Feature::Debayer, debayer);
+
+            info!("Added debayer feature");
+        }
+
+        #[cfg(feature = "quantize")]
+        {
+            let quantize = ocl::Program::builder()
+                .devices(device)
+                .src_file("programs/quantize.cl")
+                .build(&context)
+                .map_err(|source| ImgprocGpuError::ProgramBuild {
+                    feature: Feature::Quantize.name(),
+                    source,
+                })?;
+
+            programs.insert(Feature::Quantize, quantize);
+
+            info!("Added quantize feature");
+        }
+
+        Ok(Self { queue, programs })
     }
 
-    pub(crate) fn get_program(&self, f: &Feature) -> &ocl::Program {
+    pub(crate) fn get_program(&self, f: &Feature) -> Result<&ocl::Program, ImgprocGpuError> {
         self.programs
             .get(f)
-            .expect("This feature is not enabled/initialized!")
+            .ok_or_else(|| ImgprocGpuError::FeatureNotEnabled(f.name()))
     }
 
+    /// The returned image always allows partial reads/writes (an
+    /// origin/region subset of it), since `ocl::Image` places no additional
+    /// restriction on that beyond the access flags passed in `flags`; ROI
+    /// operations rely on this to read back only the rectangle they touched.
     pub fn alloc_img<T, C>(
         &self,
         img: &image::ImageBuffer<T, C>,
         flags: Option<ocl::flags::MemFlags>,
-    ) -> ocl::Image<T::Subpixel>
+    ) -> Result<ocl::Image<T::Subpixel>, ImgprocGpuError>
     where
         T: image::Pixel + 'static,
         T::Subpixel: ocl::traits::OclPrm + 'static,
         C: std::ops::Deref<Target = [T::Subpixel]>,
     {
-        use ocl::{
-            core::MemObjectType,
-            enums::{ImageChannelDataType, ImageChannelOrder},
-        };
-
         let dims = img.dimensions();
-        let (order, c_type) = match T::COLOR_TYPE {
-            image::ColorType::L8 => (
-                ImageChannelOrder::Intensity,
-                ImageChannelDataType::UnsignedInt8,
-            ),
-            image::ColorType::La8 => (
-                ImageChannelOrder::Luminance,
-                ImageChannelDataType::UnsignedInt8,
-            ),
-            image::ColorType::Rgb8 => (ImageChannelOrder::Rgb, ImageChannelDataType::UnsignedInt8),
-            image::ColorType::Rgba8 => {
-                (ImageChannelOrder::Rgba, ImageChannelDataType::UnsignedInt8)
-            }
-            image::ColorType::L16 => (
-                ImageChannelOrder::Intensity,
-                ImageChannelDataType::UnsignedInt16,
-            ),
-            image::ColorType::La16 => (
-                ImageChannelOrder::Luminance,
-                ImageChannelDataType::UnsignedInt16,
-            ),
-            image::ColorType::Rgb16 => {
-                (ImageChannelOrder::Rgb, ImageChannelDataType::UnsignedInt16)
-            }
-            image::ColorType::Rgba16 => {
-                (ImageChannelOrder::Rgba, ImageChannelDataType::UnsignedInt16)
-            }
-            image::ColorType::Bgr8 => panic!("Channel order BRG is not implemented!"),
-            image::ColorType::Bgra8 => {
-                (ImageChannelOrder::Bgra, ImageChannelDataType::UnsignedInt8)
-            }
-            image::ColorType::__NonExhaustive(_) => {
-                panic!("This channel order and channel data type combo is not implemented!")
-            }
-        };
+        let (order, c_type) = channel_order_and_type::<T>()?;
 
         let flags = match flags {
             Some(f) => f,
@@ -159,10 +216,84 @@ impl Executor {
             .copy_host_slice(&img)
             .queue(self.queue.clone())
             .build()
-            .expect("Could not allocate image on GPU!")
+            .map_err(ImgprocGpuError::ImageAlloc)
+    }
+
+    /// Uploads `img` to the device and keeps it resident there, returning a
+    /// [`GpuImage`] that chained `_gpu` operations can consume without any
+    /// further host round-trip.
+    pub fn upload<T, C>(&self, img: &image::ImageBuffer<T, C>) -> Result<GpuImage<T>, ImgprocGpuError>
+    where
+        T: image::Pixel + 'static,
+        T::Subpixel: ocl::traits::OclPrm + 'static,
+        C: std::ops::Deref<Target = [T::Subpixel]>,
+    {
+        let dims = img.dimensions();
+        let (order, c_type) = channel_order_and_type::<T>()?;
+        let ocl_img =
+            self.alloc_img(img, Some(ocl::flags::MEM_COPY_HOST_PTR | ocl::flags::MEM_READ_WRITE))?;
+
+        Ok(GpuImage::new(ocl_img, dims, order, c_type))
+    }
+
+    /// Allocates a fresh, uninitialized device image with the same dimensions
+    /// and channel layout as `like`, for `_gpu` operations that produce a new
+    /// output rather than writing in place.
+    pub(crate) fn alloc_gpu_like<P>(&self, like: &GpuImage<P>) -> Result<GpuImage<P>, ImgprocGpuError>
+    where
+        P: image::Pixel + 'static,
+        P::Subpixel: ocl::traits::OclPrm + 'static,
+    {
+        let dims = like.dimensions();
+
+        let ocl_img = ocl::Image::<P::Subpixel>::builder()
+            .channel_order(like.order())
+            .channel_data_type(like.c_type())
+            .image_type(MemObjectType::Image2d)
+            .dims(&dims)
+            .flags(ocl::flags::MEM_READ_WRITE)
+            .queue(self.queue.clone())
+            .build()
+            .map_err(ImgprocGpuError::ImageAlloc)?;
+
+        Ok(GpuImage::new(ocl_img, dims, like.order(), like.c_type()))
     }
 }
 
+pub(crate) fn channel_order_and_type<T: image::Pixel>(
+) -> Result<(ImageChannelOrder, ImageChannelDataType), ImgprocGpuError> {
+    let order_and_type = match T::COLOR_TYPE {
+        image::ColorType::L8 => (
+            ImageChannelOrder::Intensity,
+            ImageChannelDataType::UnsignedInt8,
+        ),
+        image::ColorType::La8 => (
+            ImageChannelOrder::Luminance,
+            ImageChannelDataType::UnsignedInt8,
+        ),
+        image::ColorType::Rgb8 => (ImageChannelOrder::Rgb, ImageChannelDataType::UnsignedInt8),
+        image::ColorType::Rgba8 => (ImageChannelOrder::Rgba, ImageChannelDataType::UnsignedInt8),
+        image::ColorType::L16 => (
+            ImageChannelOrder::Intensity,
+            ImageChannelDataType::UnsignedInt16,
+        ),
+        image::ColorType::La16 => (
+            ImageChannelOrder::Luminance,
+            ImageChannelDataType::UnsignedInt16,
+        ),
+        image::ColorType::Rgb16 => (ImageChannelOrder::Rgb, ImageChannelDataType::UnsignedInt16),
+        image::ColorType::Rgba16 => {
+            (ImageChannelOrder::Rgba, ImageChannelDataType::UnsignedInt16)
+        }
+        image::ColorType::Bgra8 => (ImageChannelOrder::Bgra, ImageChannelDataType::UnsignedInt8),
+        color_type @ (image::ColorType::Bgr8 | image::ColorType::__NonExhaustive(_)) => {
+            return Err(ImgprocGpuError::UnsupportedColorType(color_type))
+        }
+    };
+
+    Ok(order_and_type)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,4 +302,9 @@ mod tests {
     fn executor_default() {
         let _ = Executor::default();
     }
+
+    #[test]
+    fn executor_try_default_does_not_panic() {
+        let _ = Executor::try_default();
+    }
 }
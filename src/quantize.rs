@@ -0,0 +1,588 @@
+// Copyright 2021 Jerónimo Sánchez <jeronimosg@hotmail.es>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//   http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use image::{Rgb, RgbImage};
+
+use crate::{Executor, Feature, ImgprocGpuError};
+
+/// Dithering applied while remapping pixels to the nearest palette entry.
+pub enum DitherMode {
+    /// Nearest-palette mapping with no dithering.
+    None,
+    /// Fully parallel Bayer-matrix ordered dither.
+    Ordered,
+    /// Serial, per-row Floyd-Steinberg error diffusion. Higher quality than
+    /// [`DitherMode::Ordered`] but cannot be parallelized across rows.
+    FloydSteinberg,
+}
+
+/// Channels are quantized to this many bits before being binned, so the
+/// color histogram has `2^(CHANNEL_BITS * 3)` entries.
+const CHANNEL_BITS: u32 = 5;
+const CHANNEL_BIN_COUNT: u8 = 1 << CHANNEL_BITS;
+const HISTOGRAM_BIN_COUNT: usize = 1 << (CHANNEL_BITS * 3);
+
+fn bin_index(r: u8, g: u8, b: u8) -> usize {
+    ((r as usize) << (CHANNEL_BITS * 2)) | ((g as usize) << CHANNEL_BITS) | (b as usize)
+}
+
+/// A bounding box over quantized (5-bit) color space, as used by median cut.
+#[derive(Clone, Copy)]
+struct ColorBox {
+    r_range: (u8, u8),
+    g_range: (u8, u8),
+    b_range: (u8, u8),
+    population: u64,
+}
+
+impl ColorBox {
+    fn channel_range(&self, channel: usize) -> (u8, u8) {
+        match channel {
+            0 => self.r_range,
+            1 => self.g_range,
+            _ => self.b_range,
+        }
+    }
+
+    fn set_channel_range(&mut self, channel: usize, range: (u8, u8)) {
+        match channel {
+            0 => self.r_range = range,
+            1 => self.g_range = range,
+            _ => self.b_range = range,
+        }
+    }
+
+    fn widest_channel(&self) -> (usize, u8) {
+        let ranges = [
+            self.r_range.1 - self.r_range.0,
+            self.g_range.1 - self.g_range.0,
+            self.b_range.1 - self.b_range.0,
+        ];
+
+        (0..3)
+            .map(|c| (c, ranges[c]))
+            .max_by_key(|&(_, range)| range)
+            .unwrap()
+    }
+
+    /// Population and per-channel (population-weighted) sum, recomputed from
+    /// the histogram over this box's current bounds.
+    fn stats(&self, histogram: &[u32]) -> (u64, u64, u64, u64) {
+        let mut population = 0u64;
+        let mut sum_r = 0u64;
+        let mut sum_g = 0u64;
+        let mut sum_b = 0u64;
+
+        for r in self.r_range.0..=self.r_range.1 {
+            for g in self.g_range.0..=self.g_range.1 {
+                for b in self.b_range.0..=self.b_range.1 {
+                    let count = histogram[bin_index(r, g, b)] as u64;
+                    population += count;
+                    sum_r += count * r as u64;
+                    sum_g += count * g as u64;
+                    sum_b += count * b as u64;
+                }
+            }
+        }
+
+        (population, sum_r, sum_g, sum_b)
+    }
+
+    /// Population summed over a single value of `channel`, used to find the
+    /// weighted median to split at.
+    fn population_at(&self, histogram: &[u32], channel: usize, value: u8) -> u64 {
+        let (r_range, g_range, b_range) = (self.r_range, self.g_range, self.b_range);
+        let mut population = 0u64;
+
+        let (r_iter, g_iter, b_iter): (
+            Box<dyn Iterator<Item = u8>>,
+            Box<dyn Iterator<Item = u8>>,
+            Box<dyn Iterator<Item = u8>>,
+        ) = match channel {
+            0 => (
+                Box::new(std::iter::once(value)),
+                Box::new(g_range.0..=g_range.1),
+                Box::new(b_range.0..=b_range.1),
+            ),
+            1 => (
+                Box::new(r_range.0..=r_range.1),
+                Box::new(std::iter::once(value)),
+                Box::new(b_range.0..=b_range.1),
+            ),
+            _ => (
+                Box::new(r_range.0..=r_range.1),
+                Box::new(g_range.0..=g_range.1),
+                Box::new(std::iter::once(value)),
+            ),
+        };
+
+        for r in r_iter {
+            for g in g_iter.clone() {
+                for b in b_iter.clone() {
+                    population += histogram[bin_index(r, g, b)] as u64;
+                }
+            }
+        }
+
+        population
+    }
+
+    /// Splits this box along `channel` at its weighted median, returning the
+    /// two halves, each tightened to the range it actually occupies (so a
+    /// subsequent split sees a box's true spread rather than a boundary it
+    /// inherited but no longer has any population near).
+    fn split(&self, histogram: &[u32], channel: usize) -> (ColorBox, ColorBox) {
+        let (lo, hi) = self.channel_range(channel);
+        let half = self.population / 2;
+
+        let mut running = 0u64;
+        let mut split_at = lo;
+
+        for value in lo..=hi {
+            running += self.population_at(histogram, channel, value);
+            if running >= half {
+                split_at = value;
+                break;
+            }
+        }
+        // Keep `hi` in the right half so the two halves never overlap.
+        let split_at = split_at.min(hi - 1);
+
+        let mut left = *self;
+        left.set_channel_range(channel, (lo, split_at));
+
+        let mut right = *self;
+        right.set_channel_range(channel, ((split_at + 1).min(hi), hi));
+
+        (left.tightened(histogram), right.tightened(histogram))
+    }
+
+    /// Recomputes this box's population and narrows each channel's range to
+    /// the values it actually occupies, discarding the boundary it may have
+    /// merely inherited from its parent. A box with no population keeps its
+    /// (now meaningless) ranges; callers filter those out by population.
+    fn tightened(&self, histogram: &[u32]) -> ColorBox {
+        let (population, ..) = self.stats(histogram);
+        if population == 0 {
+            return ColorBox {
+                population: 0,
+                ..*self
+            };
+        }
+
+        ColorBox {
+            r_range: self.occupied_range(histogram, 0).unwrap(),
+            g_range: self.occupied_range(histogram, 1).unwrap(),
+            b_range: self.occupied_range(histogram, 2).unwrap(),
+            population,
+        }
+    }
+
+    /// The `(min, max)` values along `channel` that have any population
+    /// within this box's current bounds, or `None` if the box is empty.
+    fn occupied_range(&self, histogram: &[u32], channel: usize) -> Option<(u8, u8)> {
+        let (lo, hi) = self.channel_range(channel);
+
+        let mut occupied = (lo..=hi).filter(|&value| self.population_at(histogram, channel, value) > 0);
+        let min = occupied.next()?;
+        let max = occupied.last().unwrap_or(min);
+
+        Some((min, max))
+    }
+
+    fn average_color(&self, histogram: &[u32]) -> Rgb<u8> {
+        let (population, sum_r, sum_g, sum_b) = self.stats(histogram);
+
+        if population == 0 {
+            let r = (self.r_range.0 + self.r_range.1) / 2;
+            let g = (self.g_range.0 + self.g_range.1) / 2;
+            let b = (self.b_range.0 + self.b_range.1) / 2;
+            return Rgb([dequantize(r), dequantize(g), dequantize(b)]);
+        }
+
+        Rgb([
+            dequantize((sum_r / population) as u8),
+            dequantize((sum_g / population) as u8),
+            dequantize((sum_b / population) as u8),
+        ])
+    }
+}
+
+/// Maps a 5-bit bin value back to the center of its 8-bit bucket.
+fn dequantize(value: u8) -> u8 {
+    (value << (8 - CHANNEL_BITS)) | (1 << (8 - CHANNEL_BITS - 1))
+}
+
+/// Builds a palette of at most `num_colors` entries from a 32768-bin 3D
+/// color histogram using median cut: repeatedly split the box with the
+/// largest channel range, along that channel, at its weighted median. The
+/// palette has fewer entries when fewer histogram bins are occupied.
+fn median_cut_palette(histogram: &[u32], num_colors: u8) -> Vec<Rgb<u8>> {
+    let max_bin = CHANNEL_BIN_COUNT - 1;
+    let root = ColorBox {
+        r_range: (0, max_bin),
+        g_range: (0, max_bin),
+        b_range: (0, max_bin),
+        population: histogram.iter().map(|&c| c as u64).sum(),
+    }
+    .tightened(histogram);
+
+    let mut boxes = vec![root];
+
+    // A split's empty half (possible when the non-empty half's occupied
+    // range collapses to a single value) must not count towards the target:
+    // otherwise it silently consumes a split "budget" entry that a genuinely
+    // splittable box still needed.
+    while boxes.iter().filter(|b| b.population > 0).count() < num_colors as usize {
+        let splittable = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.widest_channel().1 > 0 && b.population > 0)
+            .max_by_key(|(_, b)| b.widest_channel().1);
+
+        let (index, channel) = match splittable {
+            Some((index, b)) => (index, b.widest_channel().0),
+            None => break,
+        };
+
+        let (left, right) = boxes[index].split(histogram, channel);
+        boxes[index] = left;
+        boxes.push(right);
+    }
+
+    boxes
+        .iter()
+        .filter(|b| b.population > 0)
+        .map(|b| b.average_color(histogram))
+        .collect()
+}
+
+impl Executor {
+    fn color_histogram(&self, img: &RgbImage) -> Result<Vec<u32>, ImgprocGpuError> {
+        let src = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let histogram_buffer = ocl::Buffer::<u32>::builder()
+            .queue(self.queue.clone())
+            .len(HISTOGRAM_BIN_COUNT)
+            .fill_val(0u32)
+            .build()
+            .map_err(ImgprocGpuError::BufferAlloc)?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(self.get_program(&Feature::Quantize)?)
+            .name("color_histogram")
+            .queue(self.queue.clone())
+            .global_work_size(&img.dimensions())
+            .arg(&src)
+            .arg(&histogram_buffer)
+            .build()
+            .map_err(|source| ImgprocGpuError::KernelBuild {
+                name: "color_histogram",
+                source,
+            })?;
+
+        unsafe {
+            kernel
+                .enq()
+                .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                    name: "color_histogram",
+                    source,
+                })?;
+        }
+
+        let mut histogram = vec![0u32; HISTOGRAM_BIN_COUNT];
+        histogram_buffer
+            .read(&mut histogram)
+            .enq()
+            .map_err(ImgprocGpuError::MemRead)?;
+
+        Ok(histogram)
+    }
+
+    fn palette_buffer(&self, palette: &[Rgb<u8>]) -> Result<ocl::Buffer<u8>, ImgprocGpuError> {
+        let packed: Vec<u8> = palette.iter().flat_map(|c| c.0).collect();
+
+        ocl::Buffer::<u8>::builder()
+            .queue(self.queue.clone())
+            .len(packed.len())
+            .copy_host_slice(&packed)
+            .build()
+            .map_err(ImgprocGpuError::BufferAlloc)
+    }
+
+    /// Reduces `img` to a palette of at most `num_colors` (2-255) entries,
+    /// built with median cut, and returns the nearest-palette-remapped image alongside
+    /// that palette. For a dithered remap, see [`Executor::quantize_dithered`].
+    pub fn quantize(
+        &self,
+        img: &RgbImage,
+        num_colors: u8,
+    ) -> Result<(RgbImage, Vec<Rgb<u8>>), ImgprocGpuError> {
+        self.quantize_dithered(img, num_colors, DitherMode::None)
+    }
+
+    /// Like [`Executor::quantize`], with control over the dithering applied
+    /// while remapping pixels to the palette.
+    pub fn quantize_dithered(
+        &self,
+        img: &RgbImage,
+        num_colors: u8,
+        dither: DitherMode,
+    ) -> Result<(RgbImage, Vec<Rgb<u8>>), ImgprocGpuError> {
+        assert!(num_colors >= 2, "num_colors must be at least 2");
+
+        let histogram = self.color_histogram(img)?;
+        let palette = median_cut_palette(&histogram, num_colors);
+        let palette_buffer = self.palette_buffer(&palette)?;
+
+        let src = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_READ_ONLY
+                    | ocl::flags::MEM_HOST_WRITE_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let dest = self.alloc_img(
+            img,
+            Some(
+                ocl::flags::MEM_WRITE_ONLY
+                    | ocl::flags::MEM_HOST_READ_ONLY
+                    | ocl::flags::MEM_COPY_HOST_PTR,
+            ),
+        )?;
+
+        let dims = img.dimensions();
+
+        match dither {
+            DitherMode::None => {
+                let kernel = ocl::Kernel::builder()
+                    .program(self.get_program(&Feature::Quantize)?)
+                    .name("nearest_palette")
+                    .queue(self.queue.clone())
+                    .global_work_size(&dims)
+                    .arg(&src)
+                    .arg(&dest)
+                    .arg(&palette_buffer)
+                    .arg(&(palette.len() as u32))
+                    .build()
+                    .map_err(|source| ImgprocGpuError::KernelBuild {
+                        name: "nearest_palette",
+                        source,
+                    })?;
+
+                unsafe {
+                    kernel
+                        .enq()
+                        .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                            name: "nearest_palette",
+                            source,
+                        })?;
+                }
+            }
+            DitherMode::Ordered => {
+                let kernel = ocl::Kernel::builder()
+                    .program(self.get_program(&Feature::Quantize)?)
+                    .name("nearest_palette_ordered_dither")
+                    .queue(self.queue.clone())
+                    .global_work_size(&dims)
+                    .arg(&src)
+                    .arg(&dest)
+                    .arg(&palette_buffer)
+                    .arg(&(palette.len() as u32))
+                    .build()
+                    .map_err(|source| ImgprocGpuError::KernelBuild {
+                        name: "nearest_palette_ordered_dither",
+                        source,
+                    })?;
+
+                unsafe {
+                    kernel
+                        .enq()
+                        .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                            name: "nearest_palette_ordered_dither",
+                            source,
+                        })?;
+                }
+            }
+            DitherMode::FloydSteinberg => {
+                let error_rows = ocl::Buffer::<f32>::builder()
+                    .queue(self.queue.clone())
+                    .len(2 * dims.0 as usize * 3)
+                    .fill_val(0.0f32)
+                    .build()
+                    .map_err(ImgprocGpuError::BufferAlloc)?;
+
+                for y in 0..dims.1 {
+                    let kernel = ocl::Kernel::builder()
+                        .program(self.get_program(&Feature::Quantize)?)
+                        .name("nearest_palette_floyd_steinberg_row")
+                        .queue(self.queue.clone())
+                        .global_work_size(1)
+                        .arg(&src)
+                        .arg(&dest)
+                        .arg(&palette_buffer)
+                        .arg(&(palette.len() as u32))
+                        .arg(&error_rows)
+                        .arg(&dims.0)
+                        .arg(&y)
+                        .arg(&(y % 2))
+                        .build()
+                        .map_err(|source| ImgprocGpuError::KernelBuild {
+                            name: "nearest_palette_floyd_steinberg_row",
+                            source,
+                        })?;
+
+                    unsafe {
+                        kernel
+                            .enq()
+                            .map_err(|source| ImgprocGpuError::KernelEnqueue {
+                                name: "nearest_palette_floyd_steinberg_row",
+                                source,
+                            })?;
+                    }
+                }
+            }
+        }
+
+        let mut output = image::ImageBuffer::new(dims.0, dims.1);
+        dest.read(&mut output)
+            .enq()
+            .map_err(ImgprocGpuError::MemRead)?;
+
+        Ok((output, palette))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantize_well_separated_colors_keeps_them_distinct() {
+        let executor = Executor::default();
+
+        let mut image = RgbImage::new(3, 1);
+        image.put_pixel(0, 0, Rgb([0, 0, 0]));
+        image.put_pixel(1, 0, Rgb([0, 0, 255]));
+        image.put_pixel(2, 0, Rgb([255, 0, 0]));
+
+        let (_, palette) = executor.quantize(&image, 3).unwrap();
+        assert_eq!(palette.len(), 3);
+    }
+
+    fn two_well_separated_rows_image() -> RgbImage {
+        // These are already the dequantized bin centers `median_cut_palette`
+        // would produce for a 2-color image, 248 apart on every channel: far
+        // enough that neither the ordered-dither bias (at most ±8) nor
+        // Floyd-Steinberg error diffusion (zero, since each pixel already
+        // sits exactly on its palette entry) can flip a pixel's assignment.
+        let mut image = RgbImage::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                let color = if y < 2 {
+                    Rgb([4, 4, 4])
+                } else {
+                    Rgb([252, 252, 252])
+                };
+                image.put_pixel(x, y, color);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn quantize_dithered_ordered_preserves_well_separated_colors() {
+        let executor = Executor::default();
+        let image = two_well_separated_rows_image();
+
+        let (quantized, palette) = executor
+            .quantize_dithered(&image, 2, DitherMode::Ordered)
+            .unwrap();
+
+        assert_eq!(palette.len(), 2);
+        assert_eq!(quantized, image);
+    }
+
+    #[test]
+    fn quantize_dithered_floyd_steinberg_preserves_well_separated_colors() {
+        let executor = Executor::default();
+        let image = two_well_separated_rows_image();
+
+        let (quantized, palette) = executor
+            .quantize_dithered(&image, 2, DitherMode::FloydSteinberg)
+            .unwrap();
+
+        assert_eq!(palette.len(), 2);
+        assert_eq!(quantized, image);
+    }
+
+    #[test]
+    fn median_cut_palette_of_solid_color_is_a_single_entry() {
+        let mut histogram = vec![0u32; HISTOGRAM_BIN_COUNT];
+        histogram[bin_index(31, 0, 0)] = 100;
+
+        let palette = median_cut_palette(&histogram, 4);
+        assert_eq!(palette.len(), 1);
+        assert_eq!(palette[0], Rgb([dequantize(31), dequantize(0), dequantize(0)]));
+    }
+
+    #[test]
+    fn median_cut_palette_splits_two_far_apart_colors() {
+        let mut histogram = vec![0u32; HISTOGRAM_BIN_COUNT];
+        histogram[bin_index(0, 0, 0)] = 50;
+        histogram[bin_index(31, 31, 31)] = 50;
+
+        let palette = median_cut_palette(&histogram, 2);
+        assert_eq!(palette.len(), 2);
+        assert!(palette.contains(&Rgb([dequantize(0), dequantize(0), dequantize(0)])));
+        assert!(palette.contains(&Rgb([dequantize(31), dequantize(31), dequantize(31)])));
+    }
+
+    #[test]
+    fn median_cut_palette_never_exceeds_occupied_bins() {
+        let mut histogram = vec![0u32; HISTOGRAM_BIN_COUNT];
+        histogram[bin_index(0, 0, 0)] = 10;
+        histogram[bin_index(31, 31, 31)] = 10;
+
+        let palette = median_cut_palette(&histogram, 8);
+        assert_eq!(palette.len(), 2);
+    }
+
+    #[test]
+    fn median_cut_palette_keeps_three_equally_populated_colors_distinct() {
+        // Two of these three colors share a bin on every channel but one
+        // (r=0 for the first two, b=0 for the first and last), which used to let a
+        // box with an inherited-but-unoccupied wide range win the split
+        // selection over the box that actually still needed splitting.
+        let mut histogram = vec![0u32; HISTOGRAM_BIN_COUNT];
+        histogram[bin_index(0, 0, 0)] = 100;
+        histogram[bin_index(0, 0, 31)] = 100;
+        histogram[bin_index(31, 0, 0)] = 100;
+
+        let palette = median_cut_palette(&histogram, 3);
+        assert_eq!(palette.len(), 3);
+        assert!(palette.contains(&Rgb([dequantize(0), dequantize(0), dequantize(0)])));
+        assert!(palette.contains(&Rgb([dequantize(0), dequantize(0), dequantize(31)])));
+        assert!(palette.contains(&Rgb([dequantize(31), dequantize(0), dequantize(0)])));
+    }
+}